@@ -0,0 +1,35 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// The HTTP validators we remembered from the last successful fetch of a
+/// feed, stored in a `feeds/{name}.meta` sidecar next to the `.feed` file.
+#[derive(Debug, Default, Clone)]
+pub struct FetchCache {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl FetchCache {
+    /// Loads the cache from disk, treating a missing or unreadable file as
+    /// an empty cache rather than an error.
+    pub fn load(path: &Path) -> FetchCache {
+        let mut text = String::new();
+        if File::open(path).and_then(|mut file| file.read_to_string(&mut text)).is_err() {
+            return FetchCache::default();
+        }
+
+        let mut lines = text.lines();
+        FetchCache {
+            etag: lines.next().filter(|line| !line.is_empty()).map(String::from),
+            last_modified: lines.next().filter(|line| !line.is_empty()).map(String::from),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", self.etag.as_ref().map(String::as_str).unwrap_or(""))?;
+        writeln!(file, "{}", self.last_modified.as_ref().map(String::as_str).unwrap_or(""))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use chrono::Duration;
+
+#[derive(Debug, Clone)]
+pub struct FeedInfo {
+    pub name: String,
+    pub url: String,
+    pub wait: Duration,
+    pub chunk_size: usize,
+    pub on_new: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub url: String,
+    pub title: Option<String>,
+    pub date: Option<String>,
+    pub read: bool,
+}
+
+/// A freshly-parsed feed entry, not yet known to be new or already seen.
+#[derive(Debug, Clone)]
+pub struct ParsedEntry {
+    pub url: String,
+    pub title: Option<String>,
+    pub date: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Feed {
+    pub info: FeedInfo,
+    pub items: Vec<Item>,
+}
+
+impl FeedInfo {
+    pub fn read_feed<R: Read>(&self, file: &mut R) -> Result<Feed, LoadFeedError> {
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        let items = text.lines().filter(|line| !line.is_empty()).map(Item::parse).collect();
+        Ok(Feed { info: self.clone(), items })
+    }
+}
+
+impl Item {
+    /// Parses a single `read\turl\ttitle\tdate` line from a `.feed` file.
+    fn parse(line: &str) -> Item {
+        let mut fields = line.splitn(4, '\t');
+        let read = fields.next() == Some("1");
+        let url = fields.next().map(unescape_field).unwrap_or_default();
+        let title = fields.next().map(unescape_field).filter(|field| !field.is_empty());
+        let date = fields.next().map(unescape_field).filter(|field| !field.is_empty());
+        Item { url, title, date, read }
+    }
+}
+
+/// Escapes `\`, tab, and newline so a field round-trips through the
+/// tab-separated, newline-delimited `.feed` file even when a title or date
+/// contains one of those characters verbatim.
+fn escape_field(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape_field(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+impl Feed {
+    /// Adds any entries that aren't already known to the feed, preserving the
+    /// chronological order they were fetched in. Returns the URLs that were
+    /// actually new, so callers can tell whether the feed grew.
+    pub fn add_new_comics(&mut self, entries: &[ParsedEntry]) -> Vec<String> {
+        let known: HashSet<&str> = self.items.iter().map(|item| item.url.as_str()).collect();
+        let mut added = Vec::new();
+        for entry in entries {
+            if !known.contains(entry.url.as_str()) {
+                self.items.push(Item {
+                    url: entry.url.clone(),
+                    title: entry.title.clone(),
+                    date: entry.date.clone(),
+                    read: false,
+                });
+                added.push(entry.url.clone());
+            }
+        }
+        added
+    }
+
+    fn unread(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter().filter(|item| !item.read)
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.unread().count() >= self.info.chunk_size
+    }
+
+    pub fn unread_count(&self) -> usize {
+        self.unread().count()
+    }
+
+    /// Returns the next chunk of unread comics, oldest first.
+    pub fn get_reading_list(&self) -> Vec<Item> {
+        self.unread().take(self.info.chunk_size).cloned().collect()
+    }
+
+    /// Marks the current chunk as read.
+    pub fn read(&mut self) {
+        let chunk_size = self.info.chunk_size;
+        for item in self.items.iter_mut().filter(|item| !item.read).take(chunk_size) {
+            item.read = true;
+        }
+    }
+
+    pub fn write_changes<W: Write + Seek>(&self, file: &mut W) -> Result<(), LoadFeedError> {
+        file.seek(SeekFrom::Start(0))?;
+        file.set_len(0)?;
+        for item in &self.items {
+            writeln!(
+                file,
+                "{}\t{}\t{}\t{}",
+                if item.read { "1" } else { "0" },
+                escape_field(&item.url),
+                item.title.as_ref().map(|title| escape_field(title)).unwrap_or_default(),
+                item.date.as_ref().map(|date| escape_field(date)).unwrap_or_default(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadFeedError {
+    Io(std::io::Error),
+}
+
+impl fmt::Display for LoadFeedError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadFeedError::Io(ref err) => write!(fmt, "{}", err),
+        }
+    }
+}
+
+impl From<std::io::Error> for LoadFeedError {
+    fn from(err: std::io::Error) -> LoadFeedError {
+        LoadFeedError::Io(err)
+    }
+}
+
+impl std::error::Error for LoadFeedError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadFeedError::Io(ref err) => err.description(),
+        }
+    }
+}
@@ -14,13 +14,18 @@ extern crate open;
 
 use std::io::Read;
 use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
 
-use clap::{Arg, App};
+use clap::{Arg, App, SubCommand};
 
 mod parser;
 mod feed;
+mod cache;
 
-use feed::{Feed, FeedInfo};
+use feed::{Feed, FeedInfo, Item, ParsedEntry};
+use cache::FetchCache;
 
 fn main() {
     std::process::exit(match run() {
@@ -43,6 +48,7 @@ fn run() -> Result<(), Error> {
                 .long("config")
                 .value_name("FILE")
                 .help("The config file to load feeds from")
+                .global(true)
                 .takes_value(true),
         )
         .arg(
@@ -50,22 +56,48 @@ fn run() -> Result<(), Error> {
                 .long("fetch")
                 .help("Only download feeds, don't view them"),
         )
+        .arg(
+            Arg::with_name("terminal")
+                .long("terminal")
+                .help("Print the next chunk as text instead of opening a browser"),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .long("jobs")
+                .short("j")
+                .value_name("N")
+                .help("How many feeds to fetch concurrently")
+                .global(true)
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("List unread counts for each feed without opening anything")
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print the status as a JSON array"),
+                ),
+        )
         .get_matches();
 
     let config_path = get_config(matches.value_of("config"))?;
-    let only_fetch = matches.value_of("fetch").is_some();
-
-    let feeds = {
-        let mut file = std::fs::File::open(config_path)?;
-        let mut text = String::new();
-        file.read_to_string(&mut text)?;
-        parser::parse_config(&text)?
+    let jobs = match matches.value_of("jobs") {
+        Some(n) => n.parse().map_err(|_| Error::Args(format!("invalid --jobs value: {}", n)))?,
+        None => DEFAULT_JOBS,
     };
+    let feeds = load_feeds(&config_path)?;
+    let client = reqwest::Client::new();
+
+    if let Some(status_matches) = matches.subcommand_matches("status") {
+        return run_status(&client, feeds, jobs, status_matches.is_present("json"));
+    }
 
-    // @Performance: Use hyper to fetch streams concurrently
+    let only_fetch = matches.is_present("fetch");
+    let terminal = matches.is_present("terminal");
     let mut num_read = 0;
-    for feed_info in feeds {
-        let mut feed = match fetch_feed(&feed_info) {
+    for (feed_info, result) in fetch_all(&client, feeds, jobs, true) {
+        let mut feed = match result {
             Ok(feed) => feed,
             Err(err) => {
                 println!("Error in feed {}: {}", feed_info.name, err);
@@ -75,7 +107,7 @@ fn run() -> Result<(), Error> {
 
         if feed.is_ready() && !only_fetch {
             num_read += 1;
-            if let Err(err) = read_feed(&mut feed) {
+            if let Err(err) = read_feed(&mut feed, terminal) {
                 println!("Error in feed {}: {}", feed.info.name, err);
             }
         }
@@ -89,6 +121,127 @@ fn run() -> Result<(), Error> {
     Ok(())
 }
 
+fn load_feeds(config_path: &std::path::Path) -> Result<Vec<FeedInfo>, Error> {
+    let mut file = std::fs::File::open(config_path)?;
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    Ok(parser::parse_config(&text)?)
+}
+
+struct FeedStatus {
+    name: String,
+    unread: usize,
+    ready: bool,
+}
+
+fn run_status(
+    client: &reqwest::Client,
+    feeds: Vec<FeedInfo>,
+    jobs: usize,
+    as_json: bool,
+) -> Result<(), Error> {
+    let mut statuses = Vec::new();
+    for (feed_info, result) in fetch_all(client, feeds, jobs, false) {
+        match result {
+            Ok(feed) => statuses.push(FeedStatus {
+                name: feed.info.name.clone(),
+                unread: feed.unread_count(),
+                ready: feed.is_ready(),
+            }),
+            Err(err) => println!("Error in feed {}: {}", feed_info.name, err),
+        }
+    }
+
+    if as_json {
+        let entries: Vec<String> = statuses
+            .iter()
+            .map(|status| {
+                format!(
+                    "{{\"name\":{},\"unread\":{},\"ready\":{}}}",
+                    json_string(&status.name),
+                    status.unread,
+                    status.ready
+                )
+            })
+            .collect();
+        println!("[{}]", entries.join(","));
+    } else {
+        for status in &statuses {
+            println!(
+                "{}\t{}\t{}",
+                status.name,
+                status.unread,
+                if status.ready { "ready" } else { "waiting" },
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+const DEFAULT_JOBS: usize = 8;
+
+/// Fetches every feed using a bounded pool of worker threads, returning
+/// results in the same order `feeds` was given in so the caller can still
+/// process them deterministically. When `persist` is false, feeds are
+/// fetched and parsed but no feed state or cache validators are saved and no
+/// `on_new` hooks run (a missing `.feed` file is still created empty) — used
+/// by the read-only `status` subcommand.
+fn fetch_all(
+    client: &reqwest::Client,
+    feeds: Vec<FeedInfo>,
+    jobs: usize,
+    persist: bool,
+) -> Vec<(FeedInfo, Result<Feed, Error>)> {
+    let jobs = jobs.max(1);
+    let work = Arc::new(Mutex::new(feeds.into_iter().enumerate()));
+    let (tx, rx) = mpsc::channel();
+
+    let workers: Vec<_> = (0..jobs)
+        .map(|_| {
+            let work = Arc::clone(&work);
+            let tx = tx.clone();
+            let client = client.clone();
+            thread::spawn(move || loop {
+                let next = work.lock().unwrap().next();
+                match next {
+                    Some((index, feed_info)) => {
+                        let result = fetch_feed(&client, &feed_info, persist);
+                        tx.send((index, feed_info, result)).unwrap();
+                    }
+                    None => break,
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+
+    let mut results: Vec<_> = rx.into_iter().collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    results.sort_by_key(|&(index, _, _)| index);
+    results
+        .into_iter()
+        .map(|(_, feed_info, result)| (feed_info, result))
+        .collect()
+}
+
 fn get_config(path: Option<&str>) -> Result<std::path::PathBuf, Error> {
     if let Some(path) = path {
         debug!("Using config specified on command line: {}", path);
@@ -106,12 +259,38 @@ fn get_config(path: Option<&str>) -> Result<std::path::PathBuf, Error> {
     Ok(path)
 }
 
-fn fetch_feed(feed_info: &FeedInfo) -> Result<Feed, Error> {
+fn fetch_feed(client: &reqwest::Client, feed_info: &FeedInfo, persist: bool) -> Result<Feed, Error> {
     debug!("Fetching \"{}\" from <{}>", feed_info.name, feed_info.url);
-    let mut resp = reqwest::get(&feed_info.url)?;
+
+    let meta_path = meta_file_path(feed_info)?;
+    let cache = FetchCache::load(&meta_path);
+
+    let mut request = client.get(&feed_info.url);
+    if let Some(ref etag) = cache.etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag.as_str());
+    }
+    if let Some(ref last_modified) = cache.last_modified {
+        request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified.as_str());
+    }
+
+    let mut resp = request.send()?;
+    let mut file = feed_info_file(&feed_info)?;
+
+    if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+        debug!("<{}> not modified since last fetch", feed_info.url);
+        return Ok(feed_info.read_feed(&mut file)?);
+    }
+
+    let etag = resp.headers().get(reqwest::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    let last_modified = resp.headers().get(reqwest::header::LAST_MODIFIED)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+
     let mut content = String::new();
     resp.read_to_string(&mut content)?;
-    let links: Vec<_> = {
+    let entries: Vec<ParsedEntry> = {
         use syndication::Feed;
         match Feed::from_str(&content)
             .map_err(|x| Error::ParseFeed(x.into()))? {
@@ -120,8 +299,13 @@ fn fetch_feed(feed_info: &FeedInfo) -> Result<Feed, Error> {
                 feed.entries
                     .into_iter()
                     .rev()
-                    .filter_map(|x| x.links.first().cloned())
-                    .map(|x| x.href)
+                    .filter_map(|entry| {
+                        entry.links.first().cloned().map(|link| ParsedEntry {
+                            url: link.href,
+                            title: Some(entry.title.clone()),
+                            date: Some(entry.updated.clone()),
+                        })
+                    })
                     .collect()
             }
             Feed::RSS( feed) => {
@@ -129,19 +313,59 @@ fn fetch_feed(feed_info: &FeedInfo) -> Result<Feed, Error> {
                 feed.items
                     .into_iter()
                     .rev()
-                    .filter_map(|x| x.link)
+                    .filter_map(|item| {
+                        item.link.clone().map(|link| ParsedEntry {
+                            url: link,
+                            title: item.title.clone(),
+                            date: item.pub_date.clone(),
+                        })
+                    })
                     .collect()
             }
         }
     };
 
-    let mut file = feed_info_file(&feed_info)?;
     let mut feed = feed_info.read_feed(&mut file)?;
-    feed.add_new_comics(&links);
-    feed.write_changes(&mut file)?;
+    let new_links = feed.add_new_comics(&entries);
+
+    if persist {
+        feed.write_changes(&mut file)?;
+        FetchCache { etag, last_modified }.save(&meta_path)?;
+
+        if !new_links.is_empty() {
+            run_on_new_hook(feed_info, &new_links);
+        }
+    }
+
     Ok(feed)
 }
 
+/// Runs the feed's `on_new` hook command, if it has one, passing details of
+/// the new comics through the environment. Hook failures are only logged;
+/// they must never abort the fetch.
+fn run_on_new_hook(feed_info: &FeedInfo, new_links: &[String]) {
+    let command = match feed_info.on_new {
+        Some(ref command) => command,
+        None => return,
+    };
+
+    let result = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("FEEDBURST_FEED_NAME", &feed_info.name)
+        .env("FEEDBURST_NEW_COUNT", new_links.len().to_string())
+        .env("FEEDBURST_URLS", new_links.join("\n"))
+        .status();
+
+    match result {
+        Ok(status) if !status.success() => {
+            warn!("on_new hook for \"{}\" exited with {}", feed_info.name, status);
+        }
+        Err(err) => warn!("on_new hook for \"{}\" failed to run: {}", feed_info.name, err),
+        Ok(_) => {}
+    }
+}
+
 fn feed_info_file(feed_info: &FeedInfo) -> Result<std::fs::File, Error> {
     let path = format!("feeds/{}.feed", feed_info.name);
     let path = xdg::BaseDirectories::with_prefix("feedburst")?
@@ -155,7 +379,14 @@ fn feed_info_file(feed_info: &FeedInfo) -> Result<std::fs::File, Error> {
         .map_err(From::from)
 }
 
-fn read_feed(feed: &mut Feed) -> Result<(), Error> {
+fn meta_file_path(feed_info: &FeedInfo) -> Result<std::path::PathBuf, Error> {
+    let path = format!("feeds/{}.meta", feed_info.name);
+    xdg::BaseDirectories::with_prefix("feedburst")?
+        .place_data_file(&path)
+        .map_err(From::from)
+}
+
+fn read_feed(feed: &mut Feed, terminal: bool) -> Result<(), Error> {
     let mut file = feed_info_file(&feed.info)?;
     let items = feed.get_reading_list();
     if items.len() == 0 {
@@ -167,12 +398,29 @@ fn read_feed(feed: &mut Feed) -> Result<(), Error> {
         "comics"
     };
     println!("{} ({} {})", feed.info.name, items.len(), plural_feeds);
-    open::that(items.first().unwrap())?;
+
+    if terminal {
+        print_terminal_chunk(&items);
+    } else {
+        open::that(&items.first().unwrap().url)?;
+    }
+
     feed.read();
     feed.write_changes(&mut file)?;
     Ok(())
 }
 
+fn print_terminal_chunk(items: &[Item]) {
+    for (index, item) in items.iter().enumerate() {
+        let title = item.title.as_ref().map(String::as_str).unwrap_or("(untitled)");
+        match item.date {
+            Some(ref date) => println!("{}. {} — {}", index + 1, title, date),
+            None => println!("{}. {}", index + 1, title),
+        }
+        println!("   {}", item.url);
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
@@ -182,6 +430,7 @@ impl std::fmt::Display for Error {
             Error::LoadFeed(ref err) => write!(fmt, "Error loading feed: {}", err),
             Error::ParseFeed(ref err) => write!(fmt, "Error parsing feed: {}", err),
             Error::BaseDirectory(ref err) => write!(fmt, "Error getting base dir: {}", err),
+            Error::Args(ref err) => write!(fmt, "{}", err),
         }
     }
 }
@@ -194,6 +443,7 @@ pub enum Error {
     Request(reqwest::Error),
     LoadFeed(feed::LoadFeedError),
     BaseDirectory(xdg::BaseDirectoriesError),
+    Args(String),
 }
 
 impl From<std::io::Error> for Error {
@@ -235,6 +485,7 @@ impl std::error::Error for Error {
             Error::LoadFeed(ref err) => err.description(),
             Error::ParseFeed(ref _err) => "Error parsing feed",
             Error::BaseDirectory(ref err) => err.description(),
+            Error::Args(ref err) => err,
         }
     }
 }
@@ -0,0 +1,154 @@
+use std::fmt;
+
+use chrono::Duration;
+
+use feed::FeedInfo;
+
+/// Parses a config file made up of blocks like:
+///
+/// ```text
+/// on_new notify-send "New comics" "$FEEDBURST_FEED_NAME"
+///
+/// feed "xkcd" <https://xkcd.com/rss.xml>
+///     wait 1 day
+///     chunk 1
+/// ```
+///
+/// A top-level `on_new` directive sets the default hook command run whenever
+/// any feed gains new items; a feed can override it with its own indented
+/// `on_new` directive.
+pub fn parse_config(text: &str) -> Result<Vec<FeedInfo>, ParseError> {
+    let mut feeds = Vec::new();
+    let mut global_on_new: Option<String> = None;
+    let mut lines = text.lines().enumerate().peekable();
+
+    while let Some((line_no, line)) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Some(command) = parse_on_new(trimmed) {
+            global_on_new = Some(command);
+            continue;
+        }
+
+        let (name, url) = parse_feed_header(trimmed)
+            .ok_or_else(|| ParseError::Syntax(line_no + 1, line.to_string()))?;
+
+        let mut wait = Duration::days(1);
+        let mut chunk_size = 1;
+        let mut on_new = global_on_new.clone();
+        while let Some(&(_, next)) = lines.peek() {
+            if next.trim().is_empty() || !next.starts_with(char::is_whitespace) {
+                break;
+            }
+            let (directive_line, directive) = lines.next().unwrap();
+            parse_directive(directive.trim(), &mut wait, &mut chunk_size, &mut on_new)
+                .map_err(|err| ParseError::BadDirective(directive_line + 1, err))?;
+        }
+
+        feeds.push(FeedInfo { name, url, wait, chunk_size, on_new });
+    }
+
+    if let Some(command) = global_on_new {
+        for feed in feeds.iter_mut().filter(|feed| feed.on_new.is_none()) {
+            feed.on_new = Some(command.clone());
+        }
+    }
+
+    Ok(feeds)
+}
+
+fn parse_on_new(line: &str) -> Option<String> {
+    line.strip_prefix("on_new ").map(|command| command.trim().to_string())
+}
+
+named!(feed_name<&str, &str>, delimited!(tag_s!("\""), take_until_s!("\""), tag_s!("\"")));
+named!(feed_url<&str, &str>, delimited!(tag_s!("<"), take_until_s!(">"), tag_s!(">")));
+
+named!(feed_header<&str, (&str, &str)>, do_parse!(
+    tag_s!("feed") >>
+    many1!(tag_s!(" ")) >>
+    name: feed_name >>
+    many1!(tag_s!(" ")) >>
+    url: feed_url >>
+    (name, url)
+));
+
+fn parse_feed_header(line: &str) -> Option<(String, String)> {
+    match feed_header(line) {
+        nom::IResult::Done(rest, (name, url)) if rest.trim().is_empty() => {
+            Some((name.to_string(), url.to_string()))
+        }
+        _ => None,
+    }
+}
+
+fn parse_directive(
+    directive: &str,
+    wait: &mut Duration,
+    chunk_size: &mut usize,
+    on_new: &mut Option<String>,
+) -> Result<(), String> {
+    let mut words = directive.split_whitespace();
+    match words.next() {
+        Some("wait") => {
+            *wait = parse_duration(&directive["wait".len()..])?;
+            Ok(())
+        }
+        Some("chunk") => {
+            let count = words.next().ok_or_else(|| "expected a chunk size".to_string())?;
+            *chunk_size = count.parse().map_err(|_| format!("invalid chunk size: {}", count))?;
+            Ok(())
+        }
+        Some("on_new") => {
+            let command = directive["on_new".len()..].trim();
+            if command.is_empty() {
+                return Err("expected a command after on_new".to_string());
+            }
+            *on_new = Some(command.to_string());
+            Ok(())
+        }
+        Some(other) => Err(format!("unknown directive: {}", other)),
+        None => Err("expected a directive".to_string()),
+    }
+}
+
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let mut words = text.split_whitespace();
+    let count: i64 = words.next()
+        .ok_or_else(|| "expected a duration".to_string())?
+        .parse()
+        .map_err(|_| format!("invalid duration count in: {}", text))?;
+    let unit = words.next().ok_or_else(|| "expected a duration unit".to_string())?;
+    match unit.trim_end_matches('s') {
+        "day" => Ok(Duration::days(count)),
+        "week" => Ok(Duration::weeks(count)),
+        "hour" => Ok(Duration::hours(count)),
+        other => Err(format!("unknown duration unit: {}", other)),
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    Syntax(usize, String),
+    BadDirective(usize, String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Syntax(line, ref text) => {
+                write!(fmt, "line {}: couldn't parse feed header: {}", line, text)
+            }
+            ParseError::BadDirective(line, ref err) => write!(fmt, "line {}: {}", line, err),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn description(&self) -> &str {
+        "Error parsing config"
+    }
+}